@@ -0,0 +1,36 @@
+//! Demonstrates comparing two recorded runs with
+//! [no_nonsense_flamegraphs::write_differential_flamegraph]: one "before" run and one "after" run,
+//! both captured in "folded stack" format, then rendered as a single differential flame graph
+//! where red/blue shading shows which call paths got slower/faster.
+
+use no_nonsense_flamegraphs::{span, write_differential_flamegraph, Options, OutputFormat};
+use std::thread::sleep;
+use std::time::Duration;
+
+fn work(iterations: usize) {
+    span!("work");
+    for _ in 0..iterations {
+        span!("work_unit");
+        sleep(Duration::from_millis(1));
+    }
+}
+
+fn capture(iterations: usize, output_path: &str) -> String {
+    let guard = no_nonsense_flamegraphs::init_with_options(Options {
+        format: OutputFormat::Folded,
+        output_path: output_path.into(),
+        ..Options::default()
+    });
+    work(iterations);
+    drop(guard);
+    std::fs::read_to_string(output_path).unwrap()
+}
+
+fn main() {
+    let before = capture(3, "before.folded");
+    let after = capture(7, "after.folded");
+
+    let mut svg = Vec::new();
+    write_differential_flamegraph(&before, &after, &mut svg).unwrap();
+    std::fs::write("flamegraph-differential.svg", svg).unwrap();
+}
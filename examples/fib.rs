@@ -1,28 +1,48 @@
-//! This ought to produce a flamegraph that looks like this:
+//! Recursive functions are the one case where [no_nonsense_flamegraphs::span!]'s default
+//! behavior is surprising: every call to the same call site from the same parent is merged into
+//! one node, so separate recursive calls collapse into a single wide bar. `fib(3)` without flame chart mode
+//! produces a flamegraph that looks like this:
 //!
 //!                                     [=== is_small (2 calls) ===]
 //!    [=== is_small (1 calls) ===]  [=== fib (2 calls) ===========]
 //! [=== fib (1 calls) ============================================]
+//!
+//! Setting [no_nonsense_flamegraphs::Options::flame_chart] keeps each recursive call as its own
+//! node instead, so the actual call tree is visible.
 
-use no_nonsense_flamegraphs::span;
+use no_nonsense_flamegraphs::{span, Options};
 
-fn main() {
-    // Expository purposes only. Don't ever `span!` recursive functions!
-    fn fib(n: usize) -> usize {
-        span!("fib");
+fn fib(n: usize) -> usize {
+    span!("fib");
 
-        if is_small(n) {
-            n
-        } else {
-            fib(n - 1) + fib(n - 2)
-        }
+    if is_small(n) {
+        n
+    } else {
+        fib(n - 1) + fib(n - 2)
     }
+}
 
-    fn is_small(n: usize) -> bool {
-        span!("is_small");
+fn is_small(n: usize) -> bool {
+    span!("is_small");
 
-        n <= 2
+    n <= 2
+}
+
+fn main() {
+    {
+        let _guard = no_nonsense_flamegraphs::init_with_options(Options {
+            output_path: "flamegraph.svg".into(),
+            ..Options::default()
+        });
+        fib(3);
     }
 
-    fib(3);
+    {
+        let _guard = no_nonsense_flamegraphs::init_with_options(Options {
+            flame_chart: true,
+            output_path: "flamegraph-chart.svg".into(),
+            ..Options::default()
+        });
+        fib(3);
+    }
 }
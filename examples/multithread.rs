@@ -0,0 +1,25 @@
+//! Demonstrates that a [no_nonsense_flamegraphs::FlushGuard] merges every thread's trace into one
+//! combined flame graph, instead of each thread racing to overwrite `flamegraph.svg` on its own.
+//! Each thread's calls show up under their own `thread-{id}` frame.
+
+use no_nonsense_flamegraphs::span;
+use std::thread;
+use std::time::Duration;
+
+fn worker(n: usize) {
+    span!("worker");
+    thread::sleep(Duration::from_millis(10));
+    for _ in 0..n {
+        span!("work_unit");
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn main() {
+    let _guard = no_nonsense_flamegraphs::init();
+
+    let handles: Vec<_> = (1..=4).map(|n| thread::spawn(move || worker(n))).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
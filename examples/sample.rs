@@ -8,6 +8,7 @@ use std::time::Duration;
 const LINE_WIDTH: usize = 40;
 
 fn main() {
+    let _guard = no_nonsense_flamegraphs::init();
     span!("main");
 
     let title = "Sample Flame Graph".to_owned();
@@ -0,0 +1,31 @@
+//! Demonstrates driving a flame graph from `tracing` spans instead of `span!`, via [FlameLayer].
+//! Useful if your code is already instrumented with `#[tracing::instrument]`.
+
+use no_nonsense_flamegraphs::FlameLayer;
+use std::thread::sleep;
+use std::time::Duration;
+use tracing_subscriber::layer::SubscriberExt;
+
+#[tracing::instrument]
+fn render_title(title: &str) {
+    sleep(Duration::from_millis(20));
+    println!("{}", title);
+}
+
+#[tracing::instrument]
+fn render_paragraph(paragraph: &str) {
+    sleep(Duration::from_millis(15));
+    println!("{}", paragraph);
+}
+
+fn main() {
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::registry().with(FlameLayer::new()),
+    )
+    .unwrap();
+
+    let _guard = no_nonsense_flamegraphs::init();
+
+    render_title("Sample Flame Graph");
+    render_paragraph("This is rendered via tracing spans, not span!.");
+}
@@ -0,0 +1,30 @@
+//! Demonstrates capturing a flame graph's raw "folded stack" data, via
+//! [no_nonsense_flamegraphs::Options::format] set to [no_nonsense_flamegraphs::OutputFormat::Folded],
+//! instead of rendering an SVG. Useful for checking a flame graph's data into a test, or for
+//! post-processing it yourself later (e.g. with [no_nonsense_flamegraphs::write_differential_flamegraph]).
+
+use no_nonsense_flamegraphs::{span, Options, OutputFormat};
+
+fn fib(n: usize) -> usize {
+    span!("fib");
+
+    if n <= 2 {
+        n
+    } else {
+        fib(n - 1) + fib(n - 2)
+    }
+}
+
+fn main() {
+    let output_path = "fib.folded";
+    let guard = no_nonsense_flamegraphs::init_with_options(Options {
+        format: OutputFormat::Folded,
+        output_path: output_path.into(),
+        ..Options::default()
+    });
+
+    fib(5);
+
+    drop(guard);
+    print!("{}", std::fs::read_to_string(output_path).unwrap());
+}
@@ -0,0 +1,64 @@
+//! Differential flame graphs: comparing two recorded runs to see which call paths got slower or
+//! faster between them.
+
+use inferno::flamegraph;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::io;
+
+/// Render a differential flame graph comparing a "before" and "after" run, both given in the
+/// "folded stack" format produced by [crate::Options::format] set to [crate::OutputFormat::Folded].
+///
+/// Frames are colored by how much their count changed between `before` and `after`: a stack that
+/// got slower is shaded red, one that got faster is shaded blue.
+pub fn write_differential_flamegraph<W: io::Write>(
+    before: &str,
+    after: &str,
+    w: &mut W,
+) -> io::Result<()> {
+    let before_counts = parse_folded(before);
+    let after_counts = parse_folded(after);
+    let stacks: BTreeSet<&String> = before_counts.keys().chain(after_counts.keys()).collect();
+
+    let mut differential_data = String::new();
+    for stack in stacks {
+        let before_count = before_counts.get(stack).copied().unwrap_or(0);
+        let after_count = after_counts.get(stack).copied().unwrap_or(0);
+        writeln!(differential_data, "{} {} {}", stack, before_count, after_count)
+            .expect("formatting to a String cannot fail");
+    }
+
+    let mut inferno_options = {
+        let mut options = flamegraph::Options::default();
+        options.text_truncate_direction = flamegraph::TextTruncateDirection::Right;
+        options.count_name = "μs".to_owned();
+        options
+    };
+    flamegraph::from_lines(&mut inferno_options, differential_data.lines(), w)
+        .map_err(|err| io::Error::other(err.to_string()))
+}
+
+/// Parse "folded stack" lines (`stack count`) into a map from stack to count, stripping each
+/// frame's `" (N calls)"` annotation (see `write_flame_graph_input` in `src/lib.rs`) from the key.
+/// Invocation counts almost always differ between a "before" and "after" run, so keeping them in
+/// the key would make every stack compare as distinct even when the call path itself is unchanged.
+fn parse_folded(folded: &str) -> BTreeMap<String, u64> {
+    let mut counts = BTreeMap::new();
+    for line in folded.lines() {
+        if let Some((stack, count)) = line.rsplit_once(' ') {
+            if let Ok(count) = count.parse::<u64>() {
+                counts.insert(strip_call_counts(stack), count);
+            }
+        }
+    }
+    counts
+}
+
+/// Strip each semicolon-delimited frame's `" (N calls)"` suffix, leaving just the call-site names.
+fn strip_call_counts(stack: &str) -> String {
+    stack
+        .split(';')
+        .map(|frame| frame.rsplit_once(" (").map_or(frame, |(name, _)| name))
+        .collect::<Vec<_>>()
+        .join(";")
+}
@@ -0,0 +1,129 @@
+//! A process-wide flame graph that thread-local traces are merged into while a [crate::FlushGuard]
+//! is alive. Without this, `TRACE` being `thread_local` meant every thread wrote `flamegraph.svg`
+//! independently, with the last writer winning and every other thread's data silently lost.
+
+use crate::vec_trie::{Index, VecTrie, Visitor};
+use crate::{render_flamegraph, CallSite, Measurement, Options};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Mutex, OnceLock};
+
+/// Set for as long as any [crate::FlushGuard] is alive. While set, every thread merges its trace
+/// into the global trie (below) instead of saving its own, so that the eventual save made by the
+/// guard reflects every thread's calls.
+pub(crate) static GUARD_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// The [crate::Options::flame_chart] the current guard session was started with. Stored globally
+/// (not just in the initializing thread's `TRACE`) so that every thread's collection behaves the
+/// same way, not only the thread that called `init`/`init_with_options`.
+static FLAME_CHART: AtomicBool = AtomicBool::new(false);
+
+/// Read the current guard session's [crate::Options::flame_chart].
+pub(crate) fn flame_chart() -> bool {
+    FLAME_CHART.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Set the current guard session's [crate::Options::flame_chart]. Called once by `init`/
+/// `init_with_options`, and reset to `false` when the guard session ends.
+pub(crate) fn set_flame_chart(flame_chart: bool) {
+    FLAME_CHART.store(flame_chart, std::sync::atomic::Ordering::Relaxed);
+}
+
+fn global_trie() -> &'static Mutex<VecTrie<String, Measurement>> {
+    static GLOBAL: OnceLock<Mutex<VecTrie<String, Measurement>>> = OnceLock::new();
+    GLOBAL.get_or_init(|| {
+        let mut trie = VecTrie::new();
+        seed(&mut trie);
+        Mutex::new(trie)
+    })
+}
+
+/// Give an empty trie its "threads" sentinel root. Used both to initialize the global trie and to
+/// reset it after each guard session, so a later `init()` in the same process starts fresh instead
+/// of merging on top of the previous session's data forever.
+fn seed(trie: &mut VecTrie<String, Measurement>) {
+    trie.insert_child(None, "threads".to_owned());
+}
+
+/// Merge one thread's trace into the global trie, under a `thread-{id}` frame so that per-thread
+/// breakdown remains visible in the combined flame graph.
+pub(crate) fn merge_thread_trace(root: Visitor<CallSite, Measurement>) {
+    let mut global = global_trie().lock().unwrap();
+    let threads = global
+        .root_index()
+        .expect("global trie always has a root; see global_trie()");
+    bump(&mut global, threads, root.value());
+
+    let thread_key = format!("thread-{:?}", std::thread::current().id());
+    let thread_idx = global.insert_child(Some(threads), thread_key);
+    bump(&mut global, thread_idx, root.value());
+
+    let root_idx = insert_or_push(&mut global, Some(thread_idx), root.key().to_string());
+    bump(&mut global, root_idx, root.value());
+    for child in root.children() {
+        merge_subtree(&mut global, root_idx, child);
+    }
+}
+
+fn merge_subtree(
+    dest: &mut VecTrie<String, Measurement>,
+    dest_parent: Index,
+    source: Visitor<CallSite, Measurement>,
+) {
+    let dest_idx = insert_or_push(dest, Some(dest_parent), source.key().to_string());
+    bump(dest, dest_idx, source.value());
+    for child in source.children() {
+        merge_subtree(dest, dest_idx, child);
+    }
+}
+
+/// [VecTrie::push_child] in flame chart mode (to preserve recursion and call order), otherwise
+/// [VecTrie::insert_child]. Reads the current guard session's [crate::Options::flame_chart] from
+/// [flame_chart], so every thread merges the same way regardless of which thread called `init`.
+fn insert_or_push(
+    trie: &mut VecTrie<String, Measurement>,
+    parent_idx: Option<Index>,
+    key: String,
+) -> Index {
+    if flame_chart() {
+        trie.push_child(parent_idx, key)
+    } else {
+        trie.insert_child(parent_idx, key)
+    }
+}
+
+/// Add a source node's measurement into a destination node's measurement.
+fn bump(trie: &mut VecTrie<String, Measurement>, dest_idx: Index, source: &Measurement) {
+    let dest = trie.value_mut(dest_idx);
+    dest.duration += source.duration;
+    dest.num_invocations += source.num_invocations;
+}
+
+/// Choose which node(s) to render the combined global trie from. A single thread's `thread-{id}`
+/// frame (and the `"threads"` sentinel above it) carry no information when it's the only
+/// contributor, so render its call trees directly in that case, exactly as they would have been
+/// rendered before per-thread merging existed. Once a second thread has contributed, the
+/// per-thread breakdown is genuinely informative, so render the full wrapped trie.
+fn pick_render_roots(global: &VecTrie<String, Measurement>) -> Vec<Visitor<'_, String, Measurement>> {
+    let Some(threads) = global.root() else {
+        return Vec::new();
+    };
+    let mut thread_nodes = threads.children();
+    let Some(only_thread) = thread_nodes.next() else {
+        return Vec::new();
+    };
+    if thread_nodes.next().is_some() {
+        return vec![threads];
+    }
+    only_thread.children().collect()
+}
+
+/// Render the combined global trie to `options.output_path`, the way a single thread's trie is
+/// rendered by [crate::FlameGraph]'s own `save_flamegraph`, then reset it so the next guard
+/// session (another `init()`/`init_with_options()` call in this process) starts from scratch
+/// instead of merging on top of this session's data.
+pub(crate) fn save_global_flamegraph(options: Options) {
+    let mut global = global_trie().lock().unwrap();
+    render_flamegraph(pick_render_roots(&global), &options);
+    global.clear();
+    seed(&mut global);
+}
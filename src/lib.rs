@@ -1,16 +1,26 @@
+mod differential;
+mod global;
+mod tracing_layer;
 mod vec_trie;
 
 use inferno::flamegraph;
 use std::cell::RefCell;
-use std::fmt::Write;
+use std::fmt::{self, Write as FmtWrite};
 use std::fs::File;
+use std::io::Write as IoWrite;
 use std::time::{Duration, Instant};
 use vec_trie::{Index, VecTrie, Visitor};
 
+pub use differential::write_differential_flamegraph;
+pub use tracing_layer::FlameLayer;
+
 /// Declare a span to be traced. Takes a single `&'static str` argument.
 ///
 /// The span begins when the macro is called, and ends when the guard it constructs is `drop`ed at
 /// the end of the block.
+///
+/// If your code is already instrumented with `tracing`, you don't need this macro at all: use
+/// [FlameLayer] instead.
 #[macro_export]
 macro_rules! span {
     ($name:expr) => {
@@ -47,6 +57,105 @@ impl Drop for Span {
     }
 }
 
+/*****************************************************************************
+ * Flushing                                                                  *
+ *****************************************************************************/
+
+/// Start recording a flame graph, deferring the final save until the returned [FlushGuard] is
+/// dropped. Keep the guard alive for as long as you want to keep recording, e.g. by binding it to
+/// a variable held in `main`:
+///
+/// ```
+/// let _guard = no_nonsense_flamegraphs::init();
+/// ```
+///
+/// Without a guard, the flame graph is saved (and the trace cleared) every time the span stack
+/// drains to empty, so a program with more than one top-level span would overwrite
+/// `flamegraph.svg` with each one. With a guard, all top-level spans recorded before it is
+/// dropped are merged into a single flame graph.
+///
+/// A guard also causes every thread's trace to be merged into one combined flame graph (each
+/// under its own `thread-{id}` frame, once more than one thread has recorded spans; a single
+/// thread's calls are rendered without that frame) instead of each thread racing to write
+/// `flamegraph.svg` on its own.
+///
+/// Equivalent to `init_with_options(Options::default())`.
+pub fn init() -> FlushGuard {
+    init_with_options(Options::default())
+}
+
+/// Like [init], but lets you configure how the flame graph is collected. See [Options].
+pub fn init_with_options(options: Options) -> FlushGuard {
+    global::set_flame_chart(options.flame_chart);
+    global::GUARD_ACTIVE.store(true, std::sync::atomic::Ordering::Relaxed);
+    TRACE.with(|s| s.borrow_mut().options = options);
+    FlushGuard { _private: () }
+}
+
+/// Configuration for collecting a flame graph. Passed to [init_with_options].
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Produce a "flame chart" instead of a flame graph: repeated calls to the same call site are
+    /// kept as distinct, temporally-ordered nodes instead of being merged together. Without this,
+    /// recursive functions (and any function called more than once from the same call site) have
+    /// the calls made from one parent collapsed into a single node, losing which call each nested
+    /// call came from, and call order.
+    ///
+    /// Defaults to `false`.
+    pub flame_chart: bool,
+
+    /// What to write to [Options::output_path]. Defaults to [OutputFormat::Svg].
+    pub format: OutputFormat,
+
+    /// Where to write the output. Defaults to `flamegraph.svg`.
+    pub output_path: std::path::PathBuf,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            flame_chart: false,
+            format: OutputFormat::Svg,
+            output_path: std::path::PathBuf::from("flamegraph.svg"),
+        }
+    }
+}
+
+/// The format to render a flame graph's output in. See [Options::format].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A flame graph SVG image, rendered by [inferno].
+    Svg,
+    /// The raw "folded stack" text that inferno, speedscope, and the `inferno` CLI consume: one
+    /// line per stack, each a semicolon-delimited call stack followed by a space and a count. This
+    /// is the only way to capture a flame graph's data directly (there is no API that returns it
+    /// in memory or writes it to a writer); read it back from [Options::output_path] afterward.
+    /// Two captures in this format can be compared with [write_differential_flamegraph].
+    Folded,
+}
+
+/// Defers saving the flame graph until it is dropped. Constructed by [init].
+#[must_use = "the flame graph is only saved when this guard is dropped"]
+pub struct FlushGuard {
+    _private: (),
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        let options = TRACE.with(|s| {
+            let mut trace = s.borrow_mut();
+            if let Some(root) = trace.trie.root() {
+                global::merge_thread_trace(root);
+            }
+            trace.trie.clear();
+            trace.options.clone()
+        });
+        global::GUARD_ACTIVE.store(false, std::sync::atomic::Ordering::Relaxed);
+        global::set_flame_chart(false);
+        global::save_global_flamegraph(options);
+    }
+}
+
 /*****************************************************************************
  * Global trace storage                                                      *
  *****************************************************************************/
@@ -73,9 +182,10 @@ struct StackFrame {
 }
 
 /// Global store of flame graph data.
-pub struct FlameGraph {
+struct FlameGraph {
     trie: VecTrie<CallSite, Measurement>,
     stack: Vec<StackFrame>,
+    options: Options,
 }
 
 impl FlameGraph {
@@ -83,12 +193,25 @@ impl FlameGraph {
         FlameGraph {
             trie: VecTrie::new(),
             stack: Vec::new(),
+            options: Options::default(),
         }
     }
 
     fn push_call(&mut self, call_site: &'static str) -> Index {
         let parent = self.stack.last().map(|frame| frame.index);
-        let child = self.trie.insert_child(parent, call_site);
+        // While a guard is active, every thread must collect the same way, so defer to the
+        // process-global flame_chart setting instead of this thread's own `options` (which is
+        // only set on the thread that called `init`/`init_with_options`; see `global::flame_chart`).
+        let flame_chart = if global::GUARD_ACTIVE.load(std::sync::atomic::Ordering::Relaxed) {
+            global::flame_chart()
+        } else {
+            self.options.flame_chart
+        };
+        let child = if flame_chart {
+            self.trie.push_child(parent, call_site)
+        } else {
+            self.trie.insert_child(parent, call_site)
+        };
         self.stack.push(StackFrame {
             index: child,
             start: Instant::now(),
@@ -106,58 +229,93 @@ impl FlameGraph {
             }
         }
         if self.stack.is_empty() {
-            self.save_flamegraph();
+            if global::GUARD_ACTIVE.load(std::sync::atomic::Ordering::Relaxed) {
+                // A guard is deferring output: fold this thread's trace into the combined global
+                // flame graph instead of saving (and overwriting) our own.
+                if let Some(root) = self.trie.root() {
+                    global::merge_thread_trace(root);
+                }
+            } else {
+                self.save_flamegraph();
+            }
             self.trie.clear();
         }
     }
 
     fn save_flamegraph(&self) {
-        // 1. Construct the flame graph input string.
-        let root = match self.trie.root() {
-            None => return, // nothing to save
-            Some(root) => root,
-        };
-        let mut flame_graph_data = String::new();
+        render_flamegraph(self.trie.root().into_iter().collect(), &self.options);
+    }
+}
+
+/// Build "folded stack" format text for each of the given top-level call trees (rendered one after
+/// another, as independent stacks), or `None` if there are none.
+fn folded_text<K: fmt::Display + Eq>(roots: Vec<Visitor<K, Measurement>>) -> Option<String> {
+    if roots.is_empty() {
+        return None;
+    }
+    let mut flame_graph_data = String::new();
+    for root in roots {
         let mut stack = vec![root];
         if let Err(err) = write_flame_graph_input(&mut flame_graph_data, &mut stack) {
             eprintln!(
                 "no_nonsense_flamegraphs: failed to write to string. {}",
                 err
             );
-            return;
+            return None;
         }
+    }
+    Some(flame_graph_data)
+}
 
-        // 2. Open a file for writing.
-        let mut file = match File::create("flamegraph.svg") {
-            Err(err) => {
-                eprintln!(
-                    "no_nonsense_flamegraphs: Failed to create file `flamegraph.svg`. {}",
-                    err
-                );
-                return;
-            }
-            Ok(file) => file,
-        };
+/// Render the given top-level call trees to `options.output_path`, in `options.format`.
+fn render_flamegraph<K: fmt::Display + Eq>(roots: Vec<Visitor<K, Measurement>>, options: &Options) {
+    let Some(flame_graph_data) = folded_text(roots) else {
+        return; // nothing to save
+    };
 
-        // 3. Convert the flame graph input string to an SVG image and save it as that file.
-        let mut inferno_options = {
-            let mut options = flamegraph::Options::default();
-            // How on Earth is left-truncation the default? Everybody knows that if you truncate
-            // text, you truncate on the right and put ellipses.
-            options.text_truncate_direction = flamegraph::TextTruncateDirection::Right;
-            // We're measuring time in microseconds, not "samples" like in `perf`.
-            options.count_name = "μs".to_owned();
-            options
-        };
-        if let Err(err) =
-            flamegraph::from_lines(&mut inferno_options, flame_graph_data.lines(), &mut file)
-        {
+    let mut file = match File::create(&options.output_path) {
+        Err(err) => {
             eprintln!(
-                "no_nonsense_flamegraphs: failed to construct flamegraph image. {}",
+                "no_nonsense_flamegraphs: Failed to create file `{}`. {}",
+                options.output_path.display(),
                 err
             );
             return;
         }
+        Ok(file) => file,
+    };
+
+    match options.format {
+        OutputFormat::Folded => {
+            if let Err(err) = file.write_all(flame_graph_data.as_bytes()) {
+                eprintln!(
+                    "no_nonsense_flamegraphs: failed to write folded flame graph data. {}",
+                    err
+                );
+            }
+        }
+        OutputFormat::Svg => {
+            let mut inferno_options = {
+                let mut inferno_options = flamegraph::Options::default();
+                // How on Earth is left-truncation the default? Everybody knows that if you
+                // truncate text, you truncate on the right and put ellipses.
+                inferno_options.text_truncate_direction = flamegraph::TextTruncateDirection::Right;
+                // We're measuring time in microseconds, not "samples" like in `perf`.
+                inferno_options.count_name = "μs".to_owned();
+                // In flame chart mode, lay frames out left-to-right in call order instead of
+                // alphabetically, so recursion and temporal ordering are visible.
+                inferno_options.flame_chart = options.flame_chart;
+                inferno_options
+            };
+            if let Err(err) =
+                flamegraph::from_lines(&mut inferno_options, flame_graph_data.lines(), &mut file)
+            {
+                eprintln!(
+                    "no_nonsense_flamegraphs: failed to construct flamegraph image. {}",
+                    err
+                );
+            }
+        }
     }
 }
 
@@ -166,9 +324,9 @@ impl FlameGraph {
 /// The format is a sequence of lines. Each line consists of a stack snapshot, then a space, then
 /// the duration. (The duration is in unspecified units; we use microseconds.) A stack snapshot is
 /// a sequence of stack frame labels separated by semicolons.
-fn write_flame_graph_input<W: Write>(
+fn write_flame_graph_input<W: FmtWrite, K: fmt::Display + Eq>(
     writer: &mut W,
-    stack: &mut Vec<Visitor<CallSite, Measurement>>,
+    stack: &mut Vec<Visitor<K, Measurement>>,
 ) -> Result<(), std::fmt::Error> {
     for (i, node) in stack.iter().enumerate() {
         let name = node.key();
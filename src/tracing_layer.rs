@@ -0,0 +1,61 @@
+//! Optional integration with the `tracing` ecosystem, so that code already instrumented with
+//! `#[tracing::instrument]` or `tracing::span!` produces a flame graph without needing this
+//! crate's own [crate::span!] macro.
+
+use crate::{Index, TRACE};
+use tracing::span::Id;
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// A [`tracing_subscriber::Layer`] that drives this crate's flame graph collection from
+/// `tracing` spans. Register it on a [tracing_subscriber::Registry]:
+///
+/// ```ignore
+/// use tracing_subscriber::layer::SubscriberExt;
+/// tracing::subscriber::set_global_default(
+///     tracing_subscriber::registry().with(no_nonsense_flamegraphs::FlameLayer::new()),
+/// )
+/// .unwrap();
+/// ```
+///
+/// Every entered span is pushed onto the same thread-local stack that [crate::span!] uses, so
+/// the two can be mixed freely and both feed the same flame graph.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlameLayer;
+
+impl FlameLayer {
+    /// Construct a new `FlameLayer`.
+    pub fn new() -> FlameLayer {
+        FlameLayer
+    }
+}
+
+/// The trie [Index] that a span was recorded under, stashed in the span's extensions so it can
+/// be looked up again when the span exits.
+struct SpanIndex(Index);
+
+impl<S> Layer<S> for FlameLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let name = span.metadata().name();
+        let index = TRACE.with(|trace| trace.borrow_mut().push_call(name));
+        span.extensions_mut().insert(SpanIndex(index));
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let index = span.extensions_mut().remove::<SpanIndex>();
+        if let Some(SpanIndex(index)) = index {
+            TRACE.with(|trace| trace.borrow_mut().pop_call(index));
+        }
+    }
+}
@@ -45,6 +45,15 @@ impl<K: Eq, V: Default> VecTrie<K, V> {
         })
     }
 
+    /// The [Index] of the root of the trie, if any.
+    pub fn root_index(&self) -> Option<Index> {
+        if self.0.is_empty() {
+            None
+        } else {
+            Some(Index(0))
+        }
+    }
+
     pub fn value_mut(&mut self, node_idx: Index) -> &mut V {
         &mut self.0[node_idx.0].value
     }
@@ -81,6 +90,33 @@ impl<K: Eq, V: Default> VecTrie<K, V> {
         }
     }
 
+    /// Unconditionally insert a new child, even if one with a matching key already exists.
+    ///
+    /// Unlike [VecTrie::insert_child], this never merges with an existing sibling, so repeated
+    /// calls to the same call site stay as distinct nodes. This is used in "flame chart" mode, to
+    /// preserve recursion and the temporal order of calls.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent_idx` is `None`, but the trie is non-empty. Only the root lacks a parent!
+    pub fn push_child(&mut self, parent_idx: Option<Index>, key: K) -> Index {
+        if let Some(parent_idx) = parent_idx {
+            let new_child_idx = self.push_new_node(key);
+            if let Some(mut child_idx) = self.0[parent_idx.0].first_child {
+                while let Some(idx) = self.0[child_idx].next_sibling {
+                    child_idx = idx;
+                }
+                self.0[child_idx].next_sibling = Some(new_child_idx);
+            } else {
+                self.0[parent_idx.0].first_child = Some(new_child_idx);
+            }
+            Index(new_child_idx)
+        } else {
+            assert!(self.0.is_empty());
+            Index(self.push_new_node(key))
+        }
+    }
+
     /// Empty out the entire Trie. Nothing will remain.
     pub fn clear(&mut self) {
         self.0.clear();